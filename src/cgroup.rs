@@ -0,0 +1,127 @@
+//! Transient cgroup creation for kernel-enforced memory limits.
+//!
+//! Instead of polling memory usage and killing on breach, [`Cgroup::create`] makes a cgroup
+//! with the limit written into the memory controller and [`Cgroup::add_process`] places the
+//! watched process in it, so the kernel enforces the ceiling (and OOM-kills on breach) directly.
+
+use std::{fs, io, path::PathBuf};
+
+use thiserror::Error;
+
+const CGROUP_V2_MOUNT: &str = "/sys/fs/cgroup";
+const CGROUP_V1_MEMORY_MOUNT: &str = "/sys/fs/cgroup/memory";
+
+#[derive(Debug, Error)]
+pub enum CgroupError {
+    #[error("neither cgroup v2 ({CGROUP_V2_MOUNT}/cgroup.controllers) nor cgroup v1 ({CGROUP_V1_MEMORY_MOUNT}) memory controller found")]
+    NoMemoryController,
+    #[error("failed to create cgroup directory {0}: {1}")]
+    CreateDir(PathBuf, io::Error),
+    #[error("failed to write '{1}' to {0}: {2}")]
+    Write(PathBuf, String, io::Error),
+    #[error("failed to read {0}: {1}")]
+    Read(PathBuf, io::Error),
+    #[error("failed to remove cgroup directory {0}: {1}")]
+    RemoveDir(PathBuf, io::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Version {
+    V2,
+    V1,
+}
+
+/// A transient cgroup created to enforce a memory ceiling on a single watched process.
+///
+/// Call [`Cgroup::teardown`] once the process has exited; the kernel refuses to remove a
+/// cgroup directory while a process is still attached to it.
+pub struct Cgroup {
+    path: PathBuf,
+    version: Version,
+}
+
+impl Cgroup {
+    /// Create a cgroup named `memlimit-<pid>` and write `hard` into its memory controller
+    /// (`memory.max` on v2, `memory.limit_in_bytes` on v1), with an optional `high`
+    /// throttling threshold (`memory.high`, v2 only).
+    pub fn create(pid: u32, hard: usize, high: Option<usize>) -> Result<Self, CgroupError> {
+        let version = if PathBuf::from(CGROUP_V2_MOUNT)
+            .join("cgroup.controllers")
+            .exists()
+        {
+            Version::V2
+        } else if PathBuf::from(CGROUP_V1_MEMORY_MOUNT).is_dir() {
+            Version::V1
+        } else {
+            return Err(CgroupError::NoMemoryController);
+        };
+
+        let mount = match version {
+            Version::V2 => CGROUP_V2_MOUNT,
+            Version::V1 => CGROUP_V1_MEMORY_MOUNT,
+        };
+
+        let path = PathBuf::from(mount).join(format!("memlimit-{pid}"));
+        fs::create_dir(&path).map_err(|e| CgroupError::CreateDir(path.clone(), e))?;
+
+        let cgroup = Cgroup { path, version };
+
+        match version {
+            Version::V2 => {
+                cgroup.write("memory.max", &hard.to_string())?;
+                if let Some(high) = high {
+                    cgroup.write("memory.high", &high.to_string())?;
+                }
+            }
+            Version::V1 => {
+                cgroup.write("memory.limit_in_bytes", &hard.to_string())?;
+            }
+        }
+
+        Ok(cgroup)
+    }
+
+    /// Move `pid` into this cgroup. Descendants it spawns afterwards inherit membership, so
+    /// `--children` semantics fall out for free.
+    pub fn add_process(&self, pid: u32) -> Result<(), CgroupError> {
+        self.write("cgroup.procs", &pid.to_string())
+    }
+
+    /// Number of times the kernel OOM-killed a process in this cgroup. Always `Ok(None)` on
+    /// v1, which has no `memory.events` file.
+    pub fn oom_kill_count(&self) -> Result<Option<u64>, CgroupError> {
+        if self.version == Version::V1 {
+            return Ok(None);
+        }
+
+        let contents = self.read("memory.events")?;
+        let count = contents
+            .lines()
+            .find_map(|line| line.strip_prefix("oom_kill "))
+            .and_then(|n| n.trim().parse().ok())
+            .unwrap_or(0);
+
+        Ok(Some(count))
+    }
+
+    /// Peak memory usage recorded by the kernel for this cgroup (`memory.peak`), if the
+    /// running kernel exposes it. Only available on cgroup v2.
+    pub fn peak_memory(&self) -> Option<u64> {
+        self.read("memory.peak").ok()?.trim().parse().ok()
+    }
+
+    /// Remove the cgroup directory. Must be called after the watched process has exited.
+    pub fn teardown(self) -> Result<(), CgroupError> {
+        fs::remove_dir(&self.path).map_err(|e| CgroupError::RemoveDir(self.path.clone(), e))
+    }
+
+    fn write(&self, file: &str, value: &str) -> Result<(), CgroupError> {
+        let path = self.path.join(file);
+        fs::write(&path, value).map_err(|e| CgroupError::Write(path, value.to_string(), e))
+    }
+
+    fn read(&self, file: &str) -> Result<String, CgroupError> {
+        let path = self.path.join(file);
+        fs::read_to_string(&path).map_err(|e| CgroupError::Read(path, e))
+    }
+}
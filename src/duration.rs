@@ -0,0 +1,104 @@
+//! Parsing duration arguments (`--grace`, `--interval`): a number plus a unit suffix, mirroring
+//! the chunked number+unit grammar [`crate::parse_byte_amount`] uses for byte amounts.
+
+use std::time::Duration;
+
+use itertools::Itertools;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ParseDurationError {
+    #[error("cannot parse empty string")]
+    Empty,
+    #[error("unexpected alphabetic character before amount")]
+    AlphaBeforeAmount,
+    #[error(transparent)]
+    ParseIntError(#[from] std::num::ParseIntError),
+    #[error("unknown unit '{}' (expected 'ms', 's', or 'm')", .0)]
+    UnknownUnit(String),
+    #[error("unexpected string '{}' after unit", .0)]
+    UnexpectedEnd(String),
+}
+
+/// Parse a duration like "100ms", "5s", "2m", or a bare number of milliseconds ("100").
+pub fn parse_duration(s: &str) -> Result<Duration, ParseDurationError> {
+    let s = s.trim();
+
+    let groups = s.chars().chunk_by(|c| c.is_alphabetic());
+    let mut groups = groups.into_iter().map(|(b, g)| (b, g.collect::<String>()));
+
+    let (is_alpha, group) = groups.next().ok_or(ParseDurationError::Empty)?;
+
+    let amount = if is_alpha {
+        Err(ParseDurationError::AlphaBeforeAmount)
+    } else {
+        group.parse::<u64>().map_err(Into::into)
+    }?;
+
+    if let Some((is_alpha, group)) = groups.next() {
+        assert!(is_alpha);
+
+        let duration = match group.as_str() {
+            "ms" => Duration::from_millis(amount),
+            "s" => Duration::from_secs(amount),
+            "m" => Duration::from_secs(amount * 60),
+            _ => return Err(ParseDurationError::UnknownUnit(group.clone())),
+        };
+
+        let rest = groups.map(|(_, s)| s).collect::<Vec<String>>().join("");
+
+        if rest.is_empty() {
+            Ok(duration)
+        } else {
+            Err(ParseDurationError::UnexpectedEnd(rest))
+        }
+    } else {
+        Ok(Duration::from_millis(amount))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(parse_duration(""), Err(ParseDurationError::Empty));
+    }
+
+    #[test]
+    fn test_bare_number_is_millis() {
+        assert_eq!(parse_duration("100"), Ok(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn test_millis() {
+        assert_eq!(parse_duration("100ms"), Ok(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn test_seconds() {
+        assert_eq!(parse_duration("5s"), Ok(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn test_minutes() {
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn test_unknown_unit() {
+        assert_eq!(
+            parse_duration("5h"),
+            Err(ParseDurationError::UnknownUnit(String::from("h")))
+        );
+    }
+
+    #[test]
+    fn test_stuff_after_unit() {
+        assert_eq!(
+            parse_duration("5s100hello"),
+            Err(ParseDurationError::UnexpectedEnd(String::from("100hello")))
+        );
+    }
+}
@@ -1,12 +1,21 @@
 #![doc = include_str!("../README.md")]
 
+#[cfg(target_os = "linux")]
+mod cgroup;
+mod duration;
+mod report;
+mod signal;
+
 use std::{
     collections::HashSet,
-    process::{Command, ExitCode},
+    process::{Child, Command, ExitCode},
+    time::{Duration, Instant},
 };
 
 use clap::Parser;
+use duration::parse_duration;
 use itertools::Itertools;
+use report::{OutputFormat, Report};
 use sysinfo::{Pid, System};
 use thiserror::Error;
 
@@ -26,6 +35,10 @@ enum ParseByteError {
     AmountOverflow(usize, String),
     #[error("unexpected string '{}' after unit", .0)]
     UnexpectedEnd(String),
+    #[error("percentage '{}' out of range (must be 0-100)", .0)]
+    PercentOutOfRange(u32),
+    #[error("unknown percentage base '{}' (expected 'total' or 'avail')", .0)]
+    UnknownPercentBase(String),
 }
 
 // using usize here because on 32-bit platforms it doesn't make sense to limit to >4GB of RAM
@@ -91,20 +104,142 @@ fn parse_byte_amount(s: &str) -> Result<usize, ParseByteError> {
     }
 }
 
+/// What a [`ByteSpec::Relative`] percentage is taken of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PercentBase {
+    Total,
+    Available,
+}
+
+/// A parsed `amount` argument: either an absolute byte count, or a percentage of system
+/// memory (optionally plus an absolute offset, e.g. `2G+10%`) resolved once the system's
+/// memory is known.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ByteSpec {
+    Absolute(usize),
+    Relative {
+        offset: usize,
+        pct: u8,
+        base: PercentBase,
+    },
+}
+
+impl ByteSpec {
+    /// Resolve to an absolute byte count using `sys`'s current memory snapshot.
+    fn resolve(self, sys: &System) -> usize {
+        match self {
+            ByteSpec::Absolute(n) => n,
+            ByteSpec::Relative { offset, pct, base } => {
+                let base_bytes = match base {
+                    PercentBase::Total => sys.total_memory(),
+                    PercentBase::Available => sys.available_memory(),
+                };
+                resolve_relative(offset, pct, base_bytes)
+            }
+        }
+    }
+}
+
+/// The `offset + base_bytes*pct/100` math behind [`ByteSpec::Relative`], pulled out of
+/// [`ByteSpec::resolve`] so it's testable without a live [`System`] snapshot.
+fn resolve_relative(offset: usize, pct: u8, base_bytes: u64) -> usize {
+    offset + (base_bytes as u128 * pct as u128 / 100) as usize
+}
+
+// e.g. "50%" (of total), "50%total", "50%avail", or "2G+10%"
+fn parse_byte_spec(s: &str) -> Result<ByteSpec, ParseByteError> {
+    let s = s.trim();
+
+    if let Some((abs, pct)) = s.split_once('+') {
+        let offset = parse_byte_amount(abs)?;
+        let (pct, base) = parse_percent(pct)?;
+        return Ok(ByteSpec::Relative { offset, pct, base });
+    }
+
+    if s.contains('%') {
+        let (pct, base) = parse_percent(s)?;
+        return Ok(ByteSpec::Relative {
+            offset: 0,
+            pct,
+            base,
+        });
+    }
+
+    parse_byte_amount(s).map(ByteSpec::Absolute)
+}
+
+fn parse_percent(s: &str) -> Result<(u8, PercentBase), ParseByteError> {
+    let idx = s
+        .find('%')
+        .expect("caller already confirmed '%' is present");
+    let (numeric, suffix) = s.split_at(idx);
+    let suffix = &suffix[1..]; // skip the '%' itself
+
+    let base = match suffix {
+        "" | "total" => PercentBase::Total,
+        "avail" => PercentBase::Available,
+        other => return Err(ParseByteError::UnknownPercentBase(other.to_string())),
+    };
+
+    let pct: u32 = numeric.parse()?;
+    if pct > 100 {
+        return Err(ParseByteError::PercentOutOfRange(pct));
+    }
+
+    Ok((pct as u8, base))
+}
+
 #[derive(Debug, Parser)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// The maximum amount of memory before being killed. Either in raw byte amounts (e.g. "300"), or with a unit (e.g. "300B" or "300KB" or "300KiB").
-    #[clap(value_parser = parse_byte_amount)]
-    amount: usize,
+    /// The maximum amount of memory before being killed. Either a raw byte amount (e.g.
+    /// "300", "300KB", "300KiB"), or relative to system memory (e.g. "50%", "50%avail",
+    /// "2G+10%").
+    #[clap(value_parser = parse_byte_spec)]
+    amount: ByteSpec,
     /// Monitor virtual memory instead of resident set memory.
     #[arg(name = "virtual", long)]
     virtual_mem: bool,
     /// Monitor the sum of all memory consumption from all children of the process.
     #[arg(short, long)]
     children: bool,
-    /// The command to watch
-    command: String,
+    /// Enforce the limit with a Linux cgroup instead of polling and killing. The kernel
+    /// OOM-kills the process on breach; `--virtual` is ignored since cgroups only account
+    /// resident memory.
+    #[arg(long)]
+    cgroup: bool,
+    /// With `--cgroup`, an optional throttling threshold below `amount` (written to
+    /// `memory.high` on cgroup v2). Ignored without `--cgroup` or on v1.
+    #[clap(long, value_parser = parse_byte_amount)]
+    high: Option<usize>,
+    /// Attach to an already-running process instead of spawning one. Mutually exclusive
+    /// with `command` and `--cgroup` (the cgroup backend only knows how to enforce a limit
+    /// on a process it spawned itself); the process is signalled by PID rather than killed
+    /// through an owned child handle.
+    #[arg(long, conflicts_with_all = ["command", "cgroup"])]
+    pid: Option<u32>,
+    /// A soft threshold below `amount`, parsed the same way. Once memory usage crosses it,
+    /// memlimit sends SIGTERM and starts the `--grace` timer, escalating to SIGKILL only if
+    /// usage is still above it once the timer runs out (or the hard `amount` is crossed).
+    #[clap(long, value_parser = parse_byte_spec)]
+    soft: Option<ByteSpec>,
+    /// How long to wait after a `--soft` breach before escalating from SIGTERM to SIGKILL.
+    #[clap(long, value_parser = parse_duration, default_value = "5s")]
+    grace: Duration,
+    /// Format of the run report printed on exit (peak memory, limit, whether it was hit,
+    /// exit code, elapsed time, sample count).
+    #[arg(long, value_enum, default_value = "human")]
+    format: OutputFormat,
+    /// How long to sleep between memory refreshes.
+    #[clap(long, value_parser = parse_duration, default_value = "100ms")]
+    interval: Duration,
+    /// Halve the sleep interval once usage is within 10% of `amount`, trading CPU overhead
+    /// for tighter enforcement near the threshold.
+    #[arg(long)]
+    adaptive: bool,
+    /// The command to watch. Required unless `--pid` is given.
+    #[arg(required_unless_present = "pid")]
+    command: Option<String>,
     /// Arguments to the watched command
     #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
     args: Vec<String>,
@@ -113,16 +248,79 @@ struct Args {
 fn main() -> ExitCode {
     let args = Args::parse();
 
-    let mut command = Command::new(args.command).args(args.args).spawn().unwrap();
-    let pid = Pid::from_u32(command.id());
-
     let mut sys = System::new_all();
     sys.refresh_all();
 
+    let amount = args.amount.resolve(&sys);
+    let soft = args.soft.map(|s| s.resolve(&sys));
+    let grace = args.grace;
+    let format = args.format;
+    let interval = args.interval;
+    let adaptive = args.adaptive;
+
+    if let Some(soft) = soft {
+        if soft >= amount {
+            eprintln!(
+                "memlimit: --soft ({soft} bytes) must be lower than the hard limit ({amount} bytes), otherwise it can never fire",
+            );
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if args.cgroup {
+        if soft.is_some() {
+            eprintln!(
+                "memlimit: warning: --soft/--grace are ignored with --cgroup (the kernel enforces the hard limit directly and has no soft/grace concept)",
+            );
+        }
+        return run_cgroup_mode(args, amount);
+    }
+
+    let mut owned_child = args
+        .command
+        .map(|command| Command::new(command).args(args.args).spawn().unwrap());
+
+    let pid = match &owned_child {
+        Some(child) => Pid::from_u32(child.id()),
+        None => Pid::from_u32(
+            args.pid
+                .expect("clap requires --pid when no command is given"),
+        ),
+    };
+
+    sys.refresh_all();
+
+    if owned_child.is_none() && sys.process(pid).is_none() {
+        eprintln!(
+            "memlimit: no such process: PID {} (already exited, or never existed)",
+            pid.as_u32()
+        );
+        return ExitCode::FAILURE;
+    }
+
     // list of process + childrens
     let mut hierarchy_pids = HashSet::new();
+    // when the current soft-limit breach started, so we know when the grace period is up
+    let mut soft_breach_since: Option<Instant> = None;
+
+    let start = Instant::now();
+    let mut peak_bytes = 0u64;
+    let mut samples = 0u64;
+    let mut limit_hit = false;
+
+    // set once the owned child exits normally and gets reaped inside the loop below, so we
+    // don't try to wait() on it again afterwards (sysinfo keeps reporting a zombie as present
+    // until it's reaped, so that alone can't be the loop's exit signal for an owned child)
+    let mut natural_exit: Option<std::process::ExitStatus> = None;
 
     while sys.process(pid).is_some() {
+        if let Some(child) = owned_child.as_mut() {
+            if let Some(status) = child.try_wait().unwrap() {
+                natural_exit = Some(status);
+                break;
+            }
+        }
+
         // i am the ancestor of my childrens
         hierarchy_pids.insert(pid);
 
@@ -173,29 +371,211 @@ fn main() -> ExitCode {
             })
             .sum::<u64>(); // can't overflow ever since we can't have more than 2^64 bytes of memory anyways
 
-        if mem as usize > args.amount {
-            command.kill().unwrap();
-            println!(
-                "memlimit: memory usage = {mem} bytes, higher than limit of {}, killed.",
-                args.amount
+        peak_bytes = peak_bytes.max(mem);
+        samples += 1;
+
+        if mem as usize > amount {
+            limit_hit = true;
+            terminate(&mut owned_child, pid, true);
+            eprintln!(
+                "memlimit: memory usage = {mem} bytes, higher than hard limit of {amount}, sent SIGKILL.",
             );
             break;
         }
 
+        if let Some(soft) = soft {
+            if mem as usize > soft {
+                match soft_breach_since {
+                    None => {
+                        limit_hit = true;
+                        terminate(&mut owned_child, pid, false);
+                        eprintln!(
+                            "memlimit: memory usage = {mem} bytes, higher than soft limit of {soft}, sent SIGTERM (grace {grace:?}).",
+                        );
+                        soft_breach_since = Some(Instant::now());
+                    }
+                    Some(since) if since.elapsed() >= grace => {
+                        terminate(&mut owned_child, pid, true);
+                        eprintln!(
+                            "memlimit: memory usage = {mem} bytes, still above soft limit of {soft} after grace period, sent SIGKILL.",
+                        );
+                        break;
+                    }
+                    Some(_) => {} // still within the grace period, give it a chance to shut down
+                }
+            } else {
+                // recovered below the soft limit: reset so a future breach starts a fresh timer
+                soft_breach_since = None;
+            }
+        }
+
+        let sleep = if adaptive && (mem as u128) * 10 >= (amount as u128) * 9 {
+            interval / 2
+        } else {
+            interval
+        };
+        std::thread::sleep(sleep);
+
         sys.refresh_processes();
         // clear the list since old processes aren't relevant anymore
         hierarchy_pids.clear();
     }
 
-    // return the same exit code as child
-    command
-        .wait()
-        .unwrap()
+    let (exit_code, result) = if let Some(status) = natural_exit {
+        (
+            status.code(),
+            status
+                .code()
+                .map(|e| ExitCode::from(e as u8))
+                .unwrap_or(ExitCode::SUCCESS),
+        )
+    } else {
+        match owned_child {
+            Some(mut child) => {
+                let status = child.wait().unwrap();
+                (
+                    status.code(),
+                    status
+                        .code()
+                        .map(|e| ExitCode::from(e as u8))
+                        .unwrap_or(ExitCode::SUCCESS),
+                )
+            }
+            // we didn't fork it, so there's nothing to wait() on: just wait for it to disappear
+            None => {
+                while sys.process(pid).is_some() {
+                    std::thread::sleep(interval);
+                    sys.refresh_processes();
+                }
+                (None, ExitCode::SUCCESS)
+            }
+        }
+    };
+
+    Report {
+        peak_bytes: Some(peak_bytes),
+        limit_bytes: amount,
+        limit_hit,
+        exit_code,
+        elapsed_secs: start.elapsed().as_secs_f64(),
+        samples,
+    }
+    .print(format);
+
+    result
+}
+
+/// Signal the watched process: SIGTERM when `hard` is false (graceful, soft-limit breach),
+/// SIGKILL when `hard` is true. Goes through the owned `Child` handle when we have one so its
+/// later `wait()` keeps working; otherwise signals the raw PID, which also covers SIGTERM
+/// since `Child` only exposes `kill()` (always SIGKILL).
+fn terminate(owned_child: &mut Option<Child>, pid: Pid, hard: bool) {
+    match (hard, owned_child) {
+        (true, Some(child)) => child.kill().unwrap(),
+        (true, None) => signal::kill(pid.as_u32()).unwrap(),
+        (false, _) => signal::term(pid.as_u32()).unwrap(),
+    }
+}
+
+/// Spawn `args.command` into a transient cgroup instead of polling its memory usage, so the
+/// kernel enforces the limit instead of a sampling loop racing the child's own startup.
+/// `amount` is `args.amount` already resolved against current system memory.
+///
+/// The child can't be held with a self-`SIGSTOP` in `pre_exec`: that runs before the very
+/// first `exec`, and `Command::spawn()` itself blocks on an internal exec-status pipe that
+/// only unblocks once the child execs, errors, or exits — never while it's merely stopped.
+/// Instead we exec a tiny `sh` wrapper that blocks reading from a handshake pipe (fd 3)
+/// *after* that first exec has already satisfied `spawn()`, then `exec`s into the real
+/// command once we release the pipe, having confined it to the cgroup in the meantime.
+#[cfg(target_os = "linux")]
+fn run_cgroup_mode(args: Args, amount: usize) -> ExitCode {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::process::CommandExt;
+
+    use nix::fcntl::OFlag;
+    use nix::unistd::pipe2;
+
+    let start = Instant::now();
+    let format = args.format;
+
+    let command = args
+        .command
+        .expect("--cgroup requires a command to spawn; it doesn't support --pid");
+
+    let (read_fd, write_fd) = pipe2(OFlag::O_CLOEXEC).expect("failed to create handshake pipe");
+    let read_raw = read_fd.as_raw_fd();
+
+    let mut cmd = Command::new("/bin/sh");
+    cmd.arg("-c")
+        .arg(r#"read -r _ <&3; exec "$@""#)
+        .arg("sh")
+        .arg(command)
+        .args(args.args);
+    // SAFETY: dup2 only calls the async-signal-safe dup2(2) syscall. It hands the child its
+    // own handle onto the read end of the pipe (without the close-on-exec flag this one
+    // carries), so it survives the `sh` exec above.
+    unsafe {
+        cmd.pre_exec(move || {
+            nix::unistd::dup2(read_raw, 3)
+                .map(|_| ())
+                .map_err(|e| std::io::Error::from_raw_os_error(e as i32))
+        });
+    }
+    let mut child = cmd.spawn().unwrap();
+    let pid = child.id();
+    drop(read_fd); // our copy isn't needed once the child has its own duped handle
+
+    let group = match cgroup::Cgroup::create(pid, amount, args.high) {
+        Ok(group) => group,
+        Err(e) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            panic!("failed to create cgroup for memory enforcement: {e}");
+        }
+    };
+    if let Err(e) = group.add_process(pid) {
+        let _ = child.kill();
+        let _ = child.wait();
+        panic!("failed to attach watched process to cgroup: {e}");
+    }
+
+    // now that it's confined, release the handshake so it execs into the real command
+    drop(write_fd);
+
+    let status = child.wait().unwrap();
+
+    let limit_hit = group.oom_kill_count().unwrap_or(None).unwrap_or(0) > 0;
+    if limit_hit {
+        eprintln!("memlimit: kernel OOM-killed the process (cgroup limit of {amount} bytes).");
+    }
+    let peak_bytes = group.peak_memory();
+
+    if let Err(e) = group.teardown() {
+        eprintln!("memlimit: warning: {e}");
+    }
+
+    Report {
+        peak_bytes,
+        limit_bytes: amount,
+        limit_hit,
+        exit_code: status.code(),
+        elapsed_secs: start.elapsed().as_secs_f64(),
+        samples: 0, // the kernel enforces the limit directly; we never polled
+    }
+    .print(format);
+
+    status
         .code()
         .map(|e| ExitCode::from(e as u8))
         .unwrap_or(ExitCode::SUCCESS)
 }
 
+#[cfg(not(target_os = "linux"))]
+fn run_cgroup_mode(_args: Args, _amount: usize) -> ExitCode {
+    eprintln!("memlimit: --cgroup is only supported on Linux");
+    ExitCode::FAILURE
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -277,4 +657,92 @@ mod tests {
             ))
         );
     }
+
+    #[test]
+    fn test_spec_absolute_unaffected() {
+        assert_eq!(parse_byte_spec("5KiB"), Ok(ByteSpec::Absolute(5120)));
+    }
+
+    #[test]
+    fn test_spec_percent_of_total() {
+        assert_eq!(
+            parse_byte_spec("50%"),
+            Ok(ByteSpec::Relative {
+                offset: 0,
+                pct: 50,
+                base: PercentBase::Total
+            })
+        );
+        assert_eq!(
+            parse_byte_spec("50%total"),
+            Ok(ByteSpec::Relative {
+                offset: 0,
+                pct: 50,
+                base: PercentBase::Total
+            })
+        );
+    }
+
+    #[test]
+    fn test_spec_percent_of_available() {
+        assert_eq!(
+            parse_byte_spec("50%avail"),
+            Ok(ByteSpec::Relative {
+                offset: 0,
+                pct: 50,
+                base: PercentBase::Available
+            })
+        );
+    }
+
+    #[test]
+    fn test_spec_absolute_plus_percent() {
+        assert_eq!(
+            parse_byte_spec("2G+10%"),
+            Ok(ByteSpec::Relative {
+                offset: 2_000_000_000,
+                pct: 10,
+                base: PercentBase::Total
+            })
+        );
+    }
+
+    #[test]
+    fn test_spec_percent_out_of_range() {
+        assert_eq!(
+            parse_byte_spec("150%"),
+            Err(ParseByteError::PercentOutOfRange(150))
+        );
+    }
+
+    #[test]
+    fn test_spec_unknown_percent_base() {
+        assert_eq!(
+            parse_byte_spec("50%bogus"),
+            Err(ParseByteError::UnknownPercentBase(String::from("bogus")))
+        );
+    }
+
+    #[test]
+    fn test_resolve_relative_half_of_base() {
+        assert_eq!(resolve_relative(0, 50, 1000), 500);
+    }
+
+    #[test]
+    fn test_resolve_relative_zero_percent() {
+        assert_eq!(resolve_relative(100, 0, 999), 100);
+    }
+
+    #[test]
+    fn test_resolve_relative_hundred_percent() {
+        assert_eq!(resolve_relative(0, 100, 12345), 12345);
+    }
+
+    #[test]
+    fn test_resolve_relative_offset_plus_percent() {
+        assert_eq!(
+            resolve_relative(2_000_000_000, 10, 10_000_000_000),
+            2_000_000_000 + 1_000_000_000
+        );
+    }
 }
@@ -0,0 +1,51 @@
+//! The run summary memlimit prints on every exit path (normal exit, soft/hard kill, or
+//! kernel OOM-kill under `--cgroup`), so the tool doubles as a memory-profiling harness and
+//! not just an enforcement one.
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// How to print a [`Report`].
+#[derive(Debug, Clone, Copy, ValueEnum)]
+#[value(rename_all = "lower")]
+pub enum OutputFormat {
+    /// One human-readable line (the default).
+    Human,
+    /// A single-line JSON object, for scripting in CI/benchmarking harnesses.
+    Json,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Report {
+    pub peak_bytes: Option<u64>,
+    pub limit_bytes: usize,
+    pub limit_hit: bool,
+    pub exit_code: Option<i32>,
+    pub elapsed_secs: f64,
+    pub samples: u64,
+}
+
+impl Report {
+    pub fn print(&self, format: OutputFormat) {
+        match format {
+            OutputFormat::Human => println!(
+                "memlimit: peak = {}, limit = {} bytes, limit hit = {}, exit code = {}, elapsed = {:.2}s, samples = {}",
+                self.peak_bytes
+                    .map(|p| format!("{p} bytes"))
+                    .unwrap_or_else(|| "unknown".to_string()),
+                self.limit_bytes,
+                self.limit_hit,
+                self.exit_code
+                    .map(|c| c.to_string())
+                    .unwrap_or_else(|| "unknown".to_string()),
+                self.elapsed_secs,
+                self.samples,
+            ),
+            OutputFormat::Json => println!(
+                "{}",
+                serde_json::to_string(self).expect("Report always serializes")
+            ),
+        }
+    }
+}
@@ -0,0 +1,28 @@
+//! Sending signals to a PID we don't own a [`std::process::Child`] handle for.
+//!
+//! [`Child::kill`](std::process::Child::kill) only works on processes we spawned ourselves
+//! and only sends `SIGKILL`. Attaching to an already-running process (`--pid`), and the
+//! graceful-termination escalation (`--soft`/`--grace`), both need to signal by raw PID
+//! instead.
+
+use nix::{sys::signal, unistd::Pid};
+
+/// Send `SIGTERM` to `pid`, giving it a chance to shut down cleanly.
+pub fn term(pid: u32) -> std::io::Result<()> {
+    send(pid, signal::Signal::SIGTERM)
+}
+
+/// Send `SIGKILL` to `pid`.
+pub fn kill(pid: u32) -> std::io::Result<()> {
+    send(pid, signal::Signal::SIGKILL)
+}
+
+/// Send `SIGCONT` to `pid`, resuming a process previously stopped with `SIGSTOP`.
+pub fn cont(pid: u32) -> std::io::Result<()> {
+    send(pid, signal::Signal::SIGCONT)
+}
+
+fn send(pid: u32, sig: signal::Signal) -> std::io::Result<()> {
+    signal::kill(Pid::from_raw(pid as i32), sig)
+        .map_err(|e| std::io::Error::from_raw_os_error(e as i32))
+}